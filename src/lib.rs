@@ -20,11 +20,16 @@
 //! // Symbols are ordered by their creation time
 //! assert!(x < y);
 //! ```
+//! Symbols created through [`Symbol::new`] all share one global,
+//! lock-protected symbol table. For independent symbol tables that don't
+//! contend on that lock, use a scoped [`Interner`] instead.
+//!
 //! # Similar crates
 //!
 //! - [symbol](https://crates.io/crates/symbol)
 //!
 use std::fmt::{self, Display};
+use std::ops::Deref;
 use std::sync::RwLock;
 
 use ahash::AHashMap;
@@ -33,13 +38,15 @@ use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 #[derive(Clone, Debug, Default, Eq, PartialEq)]
 struct SymbolRegister {
-    names: Vec<String>,
-    indices: AHashMap<String, usize>,
+    // names are never removed, so leaking them into `'static` storage
+    // lets us hand out `&'static str`s that outlive the registry lock
+    names: Vec<&'static str>,
+    indices: AHashMap<&'static str, usize>,
 }
 
 impl SymbolRegister {
-    fn name(&self, idx: usize) -> &str {
-        &self.names[idx]
+    fn name(&self, idx: usize) -> &'static str {
+        self.names[idx]
     }
 
     fn try_idx(&self, name: &str) -> Option<usize> {
@@ -51,73 +58,450 @@ impl SymbolRegister {
             return idx;
         }
         let new_idx = self.names.len();
-        self.indices.insert(String::from(name), new_idx);
-        self.names.push(String::from(name));
+        let name: &'static str = Box::leak(Box::<str>::from(name));
+        self.indices.insert(name, new_idx);
+        self.names.push(name);
         new_idx
     }
 }
 
+// Registers of every `Interner` that has ever been created, indexed by
+// interner id. Registers are leaked into `'static` storage (like symbol
+// names themselves) so that `Symbol::as_str`, `Display`, `Deref`, and
+// `Serialize` can resolve *any* symbol back to its name, regardless of
+// which `Interner` produced it, without that `Interner` having to still
+// be around.
+lazy_static! {
+    static ref INTERNERS: RwLock<Vec<&'static RwLock<SymbolRegister>>> =
+        RwLock::new(Vec::new());
+}
+
+fn resolve_symbol(sym: Symbol) -> &'static str {
+    let register = INTERNERS.read().unwrap()[sym.interner as usize];
+    register.read().unwrap().name(sym.idx)
+}
+
+/// A scoped, arena-backed table of interned symbol names.
+///
+/// Unlike [`Symbol::new`] and friends, which all go through one global,
+/// lock-protected register, an `Interner` owns its names exclusively, so
+/// independent computations (e.g. parallel workers) can each use their
+/// own `Interner` without contending on a shared lock. [`Symbol`]s are
+/// ordered by creation time within a single `Interner`; that guarantee
+/// does not extend across different `Interner`s.
+///
+/// Symbols produced by different `Interner`s are always distinct, even
+/// if they were interned from the same name. That said, a [`Symbol`]
+/// remains usable through its ordinary, interner-agnostic API --
+/// [`Symbol::name`], [`Symbol::as_str`], `Display`, `Deref<Target =
+/// str>`, and `Serialize` -- no matter which `Interner` produced it, not
+/// just the default global one.
+///
+/// # Example
+///
+/// ```rust
+/// use math_symbols::*;
+///
+/// let interner = Interner::new();
+/// let x = interner.intern("x");
+/// let y = interner.intern("y");
+/// assert_eq!(interner.resolve(x), "x");
+/// assert_eq!(x.as_str(), "x"); // works outside of `interner` too
+/// assert!(x < y);
+///
+/// // the same name in a different interner gives a different symbol
+/// let other = Interner::new();
+/// assert_ne!(x, other.intern("x"));
+/// ```
+#[derive(Debug)]
+pub struct Interner {
+    id: u32,
+    register: &'static RwLock<SymbolRegister>,
+}
+
+impl Interner {
+    /// Construct a new, empty interner
+    pub fn new() -> Self {
+        let register: &'static RwLock<SymbolRegister> =
+            Box::leak(Box::new(RwLock::new(SymbolRegister::default())));
+        let mut interners = INTERNERS.write().unwrap();
+        let id = interners.len() as u32;
+        interners.push(register);
+        Self { id, register }
+    }
+
+    /// Intern `name`, returning the [`Symbol`] for it
+    pub fn intern<S: AsRef<str>>(&self, name: S) -> Symbol {
+        let name = name.as_ref();
+        if let Some(idx) = self.register.read().unwrap().try_idx(name) {
+            return Symbol {
+                interner: self.id,
+                idx,
+            };
+        }
+        let idx = self.register.write().unwrap().idx(name);
+        Symbol {
+            interner: self.id,
+            idx,
+        }
+    }
+
+    /// Resolve a symbol previously interned by this `Interner` back to
+    /// its name.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `sym` was not produced by this `Interner`. Use
+    /// [`Symbol::as_str`] to resolve a symbol without knowing (or
+    /// checking) which `Interner` produced it.
+    pub fn resolve(&self, sym: Symbol) -> &'static str {
+        assert_eq!(
+            sym.interner, self.id,
+            "symbol was not interned by this Interner"
+        );
+        self.register.read().unwrap().name(sym.idx)
+    }
+}
+
+impl Default for Interner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 lazy_static! {
-    static ref SYMBOL_REGISTER: RwLock<SymbolRegister> =
-        RwLock::new(SymbolRegister::default());
+    static ref GLOBAL_INTERNER: Interner = Interner::new();
 }
 
 /// A symbol
-#[derive(
-    Copy,
-    Clone,
-    Debug,
-    Default,
-    Eq,
-    PartialEq,
-    Ord,
-    PartialOrd,
-    Hash,
-    Deserialize,
-    Serialize,
-)]
-#[serde(transparent)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub struct Symbol {
-    #[serde(
-        serialize_with = "serialize_sym",
-        deserialize_with = "deserialize_sym"
-    )]
+    interner: u32,
     idx: usize,
 }
 
+impl Default for Symbol {
+    fn default() -> Self {
+        Symbol::new("")
+    }
+}
+
 impl Symbol {
-    /// Construct a symbol with the given name
+    /// Construct a symbol with the given name in the default, global
+    /// interner.
+    ///
+    /// This is a convenience wrapper around [`Interner::intern`] for the
+    /// common case of a single, process-wide symbol table. Use
+    /// [`Interner`] directly for independent, scoped symbol tables.
     pub fn new<S: AsRef<str>>(name: S) -> Self {
-        let name = name.as_ref();
-        if let Some(idx) = SYMBOL_REGISTER.read().unwrap().try_idx(name) {
-            return Self { idx };
-        }
-        let idx = SYMBOL_REGISTER.write().unwrap().idx(name);
-        Self { idx }
+        GLOBAL_INTERNER.intern(name)
     }
 
     /// Get the symbol's name
     pub fn name(&self) -> String {
-        SYMBOL_REGISTER.read().unwrap().name(self.idx).to_owned()
+        self.as_str().to_owned()
+    }
+
+    /// Get the symbol's name without allocating.
+    ///
+    /// Works for a symbol from any [`Interner`], not just the default
+    /// global one. Names are never removed from a registry, so the
+    /// returned `&str` is valid for the lifetime of the program.
+    pub fn as_str(&self) -> &'static str {
+        resolve_symbol(*self)
+    }
+
+    /// Snapshot the names in the global registry, in creation order.
+    ///
+    /// Together with [`Symbol::load_registry`], this lets two processes
+    /// agree on identical symbol indices -- and therefore identical
+    /// [`Ord`] "creation time" ordering -- before exchanging index-based
+    /// serialized data. (A [`SymbolDict`] does not need this: it
+    /// serializes names, and its ids are local to the dictionary.)
+    pub fn dump_registry() -> Vec<String> {
+        GLOBAL_INTERNER
+            .register
+            .read()
+            .unwrap()
+            .names
+            .iter()
+            .map(|name| name.to_string())
+            .collect()
+    }
+
+    /// Repopulate the global registry from a snapshot previously taken
+    /// with [`Symbol::dump_registry`].
+    ///
+    /// Names are registered in order under a single write lock, so a
+    /// process that loads the same names in the same order ends up with
+    /// identical indices -- but only if the registry doesn't already
+    /// contain a conflicting name at one of those indices. Call this
+    /// before any other interning happens on this process (e.g. before
+    /// any call to [`Symbol::new`]).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the registry already contains names that don't match
+    /// `names`' positions, since that would silently desynchronize the
+    /// indices this API exists to keep aligned.
+    pub fn load_registry(names: &[String]) {
+        symbols_from(names);
+        let dump = Self::dump_registry();
+        let prefix = dump.get(..names.len());
+        assert_eq!(
+            prefix,
+            Some(names),
+            "Symbol::load_registry: the global registry already diverged \
+             from this snapshot; load_registry must run before any other \
+             interning on this process"
+        );
+    }
+}
+
+/// Intern many names at once, taking the write lock on the global
+/// registry only once instead of once per name.
+pub fn symbols_from<I, S>(names: I) -> Vec<Symbol>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let mut register = GLOBAL_INTERNER.register.write().unwrap();
+    names
+        .into_iter()
+        .map(|name| Symbol {
+            interner: GLOBAL_INTERNER.id,
+            idx: register.idx(name.as_ref()),
+        })
+        .collect()
+}
+
+impl Deref for Symbol {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
     }
 }
 
 impl Display for Symbol {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", SYMBOL_REGISTER.read().unwrap().name(self.idx))
+        write!(f, "{}", self.as_str())
     }
 }
 
-fn serialize_sym<S: Serializer>(sym: &usize, s: S) -> Result<S::Ok, S::Error> {
-    let sym = Symbol { idx: *sym };
-    let name = sym.name();
-    String::serialize(&name, s)
+impl Serialize for Symbol {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        str::serialize(self.as_str(), s)
+    }
+}
+
+impl<'de> Deserialize<'de> for Symbol {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(d)?;
+        Ok(Symbol::new(name))
+    }
 }
 
-fn deserialize_sym<'de, D: Deserializer<'de>>(d: D) -> Result<usize, D::Error> {
-    let name = String::deserialize(d)?;
-    let s = Symbol::new(&name);
-    Ok(s.idx)
+// Marker passed to `serialize_newtype_struct`/`deserialize_newtype_struct`
+// so that formats which natively distinguish symbols from strings (such
+// as Preserves) can recognize a `SymbolAsToken` and emit or require their
+// own symbol token, instead of a generic string. Formats without such a
+// distinction (JSON, bincode, ...) just see the wrapped name.
+const SYMBOL_TOKEN: &str = "$math_symbols::Symbol";
+
+/// Wraps a [`Symbol`] so it serializes as a distinct *symbol* token
+/// rather than a plain string, for self-describing formats that tell
+/// the two apart (e.g. [Preserves](https://preserves.dev/)).
+///
+/// This mirrors how Preserves' own `Symbol` type serializes to
+/// `IOValue::symbol(...)` and rejects plain string values on the way
+/// back. Formats that have no notion of a distinct symbol token treat
+/// this exactly like [`Symbol`]'s own, plain string serialization.
+///
+/// # Example
+///
+/// ```rust
+/// use math_symbols::*;
+///
+/// let x = SymbolAsToken(Symbol::new("x"));
+/// let json = serde_json::to_string(&x).unwrap();
+/// let back: SymbolAsToken = serde_json::from_str(&json).unwrap();
+/// assert_eq!(x, back);
+/// ```
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct SymbolAsToken(pub Symbol);
+
+impl Serialize for SymbolAsToken {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_newtype_struct(SYMBOL_TOKEN, self.0.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for SymbolAsToken {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        struct TokenVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for TokenVisitor {
+            type Value = SymbolAsToken;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a symbol")
+            }
+
+            fn visit_newtype_struct<D: Deserializer<'de>>(
+                self,
+                d: D,
+            ) -> Result<Self::Value, D::Error> {
+                let name = String::deserialize(d)?;
+                Ok(SymbolAsToken(Symbol::new(name)))
+            }
+        }
+
+        d.deserialize_newtype_struct(SYMBOL_TOKEN, TokenVisitor)
+    }
+}
+
+/// Sentinel used in [`SymbolDict::ids`] to mark a registry index that has
+/// not yet been assigned a local id.
+const UNASSIGNED: usize = usize::MAX;
+
+/// A single entry in the wire format produced by [`SymbolDict`]: either a
+/// symbol's name, written out the first time it is encoded, or a compact
+/// back-reference to a symbol that was already written earlier in the
+/// same dictionary.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum SymEntry {
+    /// The name of a symbol seen for the first time by this dictionary
+    New(String),
+    /// A back-reference to the `id`-th symbol previously written by this
+    /// dictionary
+    Ref(u32),
+}
+
+/// A dictionary that assigns symbols sequential, local ids in first-seen
+/// order, so that a name only has to be written out once when
+/// serializing many [`Symbol`]s that repeat (e.g. in a large expression
+/// tree).
+///
+/// A [`SymbolDict`] can itself be serialized and deserialized. Doing so
+/// allows a dictionary built up during one session to be preshared and
+/// reused in a later one, so that the names it already knows about never
+/// have to be written out again.
+///
+/// A `SymbolDict` only supports symbols from the default, global
+/// interner (the ones [`Symbol::new`] produces); like [`Interner::resolve`],
+/// [`SymbolDict::encode`] panics if given a symbol from a scoped
+/// [`Interner`], since its local ids are keyed purely off the global
+/// registry index and would otherwise collide across interners.
+///
+/// # Example
+///
+/// ```rust
+/// use math_symbols::*;
+///
+/// let x = Symbol::new("x");
+/// let mut dict = SymbolDict::new();
+/// assert!(matches!(dict.encode(x), SymEntry::New(_)));
+/// assert!(matches!(dict.encode(x), SymEntry::Ref(0)));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct SymbolDict {
+    // maps a global registry index to the local id it was assigned by
+    // this dictionary, or `UNASSIGNED` if it hasn't been seen yet
+    ids: Vec<usize>,
+    // symbols in the order they were assigned a local id
+    symbols: Vec<Symbol>,
+}
+
+impl SymbolDict {
+    /// Construct an empty dictionary
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Encode `sym`, assigning it the next local id if this is the first
+    /// time this dictionary has seen it
+    ///
+    /// # Panics
+    ///
+    /// Panics if `sym` was not interned by the default, global interner
+    /// (see the type-level docs).
+    pub fn encode(&mut self, sym: Symbol) -> SymEntry {
+        assert_eq!(
+            sym.interner, GLOBAL_INTERNER.id,
+            "SymbolDict only supports symbols from the default, global interner"
+        );
+        if sym.idx >= self.ids.len() {
+            self.ids.resize(sym.idx + 1, UNASSIGNED);
+        }
+        let id = self.ids[sym.idx];
+        if id != UNASSIGNED {
+            return SymEntry::Ref(id as u32);
+        }
+        self.ids[sym.idx] = self.symbols.len();
+        self.symbols.push(sym);
+        SymEntry::New(sym.name())
+    }
+
+    /// Decode an entry produced by [`SymbolDict::encode`], growing the
+    /// dictionary if it introduces a new symbol.
+    ///
+    /// Since entries are meant to cross a serialization boundary, an
+    /// out-of-range `Ref` (e.g. from a malformed or out-of-sync payload)
+    /// is reported as an error instead of panicking.
+    pub fn decode(&mut self, entry: SymEntry) -> Result<Symbol, DecodeError> {
+        match entry {
+            SymEntry::New(name) => {
+                let sym = Symbol::new(name);
+                self.symbols.push(sym);
+                Ok(sym)
+            }
+            SymEntry::Ref(id) => self
+                .symbols
+                .get(id as usize)
+                .copied()
+                .ok_or(DecodeError { id }),
+        }
+    }
+}
+
+/// Error returned by [`SymbolDict::decode`] when given a [`SymEntry::Ref`]
+/// that doesn't refer to a previously decoded symbol.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct DecodeError {
+    id: u32,
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SymbolDict: no symbol registered for id {}", self.id)
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+impl Serialize for SymbolDict {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        let names: Vec<String> =
+            self.symbols.iter().map(Symbol::name).collect();
+        names.serialize(s)
+    }
+}
+
+impl<'de> Deserialize<'de> for SymbolDict {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let names = Vec::<String>::deserialize(d)?;
+        let mut dict = SymbolDict::new();
+        for name in names {
+            let sym = Symbol::new(name);
+            if sym.idx >= dict.ids.len() {
+                dict.ids.resize(sym.idx + 1, UNASSIGNED);
+            }
+            dict.ids[sym.idx] = dict.symbols.len();
+            dict.symbols.push(sym);
+        }
+        Ok(dict)
+    }
 }
 
 /// Construct variables with the same variable and symbol name
@@ -143,4 +527,115 @@ mod tests {
         let xx = Symbol::new("x");
         assert_eq!(xx.name(), "x");
     }
+
+    #[test]
+    fn as_str() {
+        symbols!(x);
+        assert_eq!(x.as_str(), "x");
+        assert_eq!(&*x, "x");
+    }
+
+    #[test]
+    fn interner() {
+        let a = Interner::new();
+        let b = Interner::new();
+        let ax = a.intern("x");
+        let ay = a.intern("y");
+        let bx = b.intern("x");
+        assert_eq!(a.resolve(ax), "x");
+        assert!(ax < ay);
+        assert_ne!(ax, bx);
+    }
+
+    #[test]
+    #[should_panic]
+    fn interner_resolve_foreign_symbol() {
+        let a = Interner::new();
+        let b = Interner::new();
+        b.resolve(a.intern("x"));
+    }
+
+    #[test]
+    fn scoped_symbol_is_usable_outside_its_interner() {
+        let scoped = Interner::new();
+        let x = scoped.intern("scoped_x");
+
+        // name()/as_str()/Deref don't require the originating Interner
+        assert_eq!(x.name(), "scoped_x");
+        assert_eq!(x.as_str(), "scoped_x");
+        assert_eq!(&*x, "scoped_x");
+        assert_eq!(format!("{}", x), "scoped_x");
+
+        let json = serde_json::to_string(&x).unwrap();
+        assert_eq!(json, "\"scoped_x\"");
+    }
+
+    #[test]
+    fn bulk_intern() {
+        let syms = symbols_from(["bulk_a", "bulk_b", "bulk_a"]);
+        assert_eq!(syms[0], Symbol::new("bulk_a"));
+        assert_eq!(syms[1], Symbol::new("bulk_b"));
+        assert_eq!(syms[2], syms[0]);
+    }
+
+    #[test]
+    fn dump_and_load_registry() {
+        symbols!(dump_x, dump_y);
+        let dump = Symbol::dump_registry();
+        assert!(dump.contains(&dump_x.name()));
+        assert!(dump.contains(&dump_y.name()));
+
+        Symbol::load_registry(&dump);
+        assert_eq!(Symbol::dump_registry(), dump);
+    }
+
+    #[test]
+    #[should_panic]
+    fn load_registry_detects_divergence() {
+        // make sure index 0 is already taken by a different name
+        Symbol::new("load_registry_divergence_first");
+        Symbol::load_registry(&["not-actually-at-this-index".to_string()]);
+    }
+
+    #[test]
+    fn symbol_as_token() {
+        symbols!(x);
+        let tok = SymbolAsToken(x);
+        let json = serde_json::to_string(&tok).unwrap();
+        assert_eq!(json, "\"x\"");
+        let back: SymbolAsToken = serde_json::from_str(&json).unwrap();
+        assert_eq!(tok, back);
+    }
+
+    #[test]
+    fn symbol_dict() {
+        symbols!(a, b, c);
+        let mut enc = SymbolDict::new();
+        let ea = enc.encode(a);
+        let eb = enc.encode(b);
+        let ea2 = enc.encode(a);
+        assert!(matches!(ea, SymEntry::New(_)));
+        assert!(matches!(eb, SymEntry::New(_)));
+        assert!(matches!(ea2, SymEntry::Ref(0)));
+
+        let mut dec = SymbolDict::new();
+        assert_eq!(dec.decode(ea).unwrap(), a);
+        assert_eq!(dec.decode(eb).unwrap(), b);
+        assert_eq!(dec.decode(ea2).unwrap(), a);
+        assert!(dec.decode(SymEntry::Ref(99)).is_err());
+
+        let mut shared: SymbolDict =
+            serde_json::from_str(&serde_json::to_string(&enc).unwrap())
+                .unwrap();
+        assert!(matches!(shared.encode(c), SymEntry::New(_)));
+        assert!(matches!(shared.encode(a), SymEntry::Ref(0)));
+    }
+
+    #[test]
+    #[should_panic]
+    fn symbol_dict_rejects_foreign_interner() {
+        let scoped = Interner::new();
+        let mut dict = SymbolDict::new();
+        dict.encode(scoped.intern("x"));
+    }
 }